@@ -1,14 +1,35 @@
 use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashSet;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, BufWriter, Write, Read};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::Instant;
 use ignore::WalkBuilder;
 use chardet;
 use encoding_rs;
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use tar::{Archive, Builder};
+
+/// Fixed key used to seed the SipHash-1-3 fingerprint of each line.
+///
+/// The key only needs to be stable across a single run (and ideally across
+/// runs, so that `--exact` and fingerprint mode agree on what a "duplicate"
+/// is); it is not a security boundary.
+const FINGERPRINT_KEY: (u64, u64) = (0x5bd1_e995_9e37_79b9, 0xc2b2_ae35_27d4_eb4f);
+
+/// Computes the 128-bit SipHash-1-3 fingerprint of a line's bytes.
+fn fingerprint(bytes: &[u8]) -> u128 {
+    let mut hasher = SipHasher13::new_with_keys(FINGERPRINT_KEY.0, FINGERPRINT_KEY.1);
+    hasher.write(bytes);
+    hasher.finish128().as_u128()
+}
 
 /// A tool to deduplicate lines from files.
 #[derive(Parser, Debug)]
@@ -33,6 +54,70 @@ struct Args {
     /// Globs of files/directories to ignore. Can be used multiple times.
     #[arg(long)]
     ignore: Vec<String>,
+
+    /// Number of files to process concurrently when using --directory. Ignored (falls
+    /// back to 1) when output goes to stdout, since concurrent workers would
+    /// interleave their output.
+    #[arg(short, long, default_value_t = num_cpus::get())]
+    jobs: usize,
+
+    /// Compare full line contents instead of 128-bit fingerprints.
+    ///
+    /// Fingerprint-based dedup (the default) keeps only a 16-byte SipHash-1-3
+    /// digest per unique line, which bounds memory use on huge inputs at the
+    /// cost of an astronomically small chance of a hash collision. Pass this
+    /// flag to fall back to exact string comparison when that risk is
+    /// unacceptable.
+    #[arg(long)]
+    exact: bool,
+
+    /// Prefix each emitted line with the number of times it occurred.
+    #[arg(short, long)]
+    count: bool,
+
+    /// Fold case when comparing lines for duplicates, keeping the first-seen casing.
+    #[arg(short = 'i', long)]
+    ignore_case: bool,
+
+    /// Only collapse consecutive duplicate lines instead of deduplicating globally.
+    #[arg(short, long)]
+    adjacent: bool,
+
+    /// Read and write NUL-delimited records instead of newline-delimited lines.
+    ///
+    /// Lets `dedupler` sit safely in pipelines like `find -print0 | dedupler -0`
+    /// where records may contain embedded newlines.
+    #[arg(short = '0', long)]
+    nul: bool,
+
+    /// When the input is a tar (or tar.gz) archive, dedup across all entries as one
+    /// logical stream instead of deduplicating each entry independently.
+    #[arg(long)]
+    merge: bool,
+
+    /// Find duplicate *files* under --directory instead of deduplicating lines.
+    #[arg(long)]
+    files: bool,
+
+    /// With --files, replace redundant copies with hard links to the kept canonical file.
+    #[arg(long, conflicts_with = "delete")]
+    link: bool,
+
+    /// With --files, remove redundant copies instead of only reporting them.
+    #[arg(long)]
+    delete: bool,
+
+    /// Directory for temporary bucket files used by the disk-spilling dedup path that
+    /// kicks in once --max-memory is exceeded. Defaults to the system temp directory.
+    #[arg(long)]
+    tempdir: Option<PathBuf>,
+
+    /// Approximate number of bytes to hold in the in-memory dedup set (16 bytes per
+    /// fingerprint, or the line length in --exact mode) before abandoning the
+    /// single-pass approach for an external, disk-spilling one. Only applies to the
+    /// default dedup mode, not --count or --adjacent.
+    #[arg(long, default_value_t = 256 * 1024 * 1024)]
+    max_memory: usize,
 }
 
 /// Execution statistics for a file processing operation.
@@ -41,54 +126,101 @@ struct Stats {
     total_lines: u64,
     duplicate_lines: u64,
     lines_written: u64,
+    distinct_groups: u64,
     duration: std::time::Duration,
 }
 
+/// Controls how duplicate lines are identified and emitted, shared by the
+/// single-file and directory processing paths.
+#[derive(Debug, Clone, Copy, Default)]
+struct DedupOptions {
+    /// Compare full line contents instead of 128-bit fingerprints.
+    exact: bool,
+    /// Prefix each emitted line with the number of times it occurred.
+    count: bool,
+    /// Fold case when comparing lines, keeping the first-seen casing.
+    ignore_case: bool,
+    /// Only collapse consecutive duplicate lines instead of deduplicating globally.
+    adjacent: bool,
+    /// Use NUL instead of newline as the record delimiter.
+    nul: bool,
+    /// Dedup across all entries of a tar archive as one logical stream.
+    merge: bool,
+}
+
 fn main() -> io::Result<()> {
     let args = Args::parse();
     let start_time = Instant::now();
     let mut total_stats = Stats::default();
 
-    if let Some(dir_path) = args.directory {
-        // Process a directory
-        let mut walk_builder = WalkBuilder::new(&dir_path);
-        walk_builder.hidden(false); // Process hidden files by default unless ignored
+    if args.files {
+        let dir_path = match &args.directory {
+            Some(dir_path) => dir_path,
+            None => {
+                eprintln!("Error: --files requires an input directory via -d/--directory.");
+                std::process::exit(1);
+            }
+        };
 
-        for pattern in &args.ignore {
-            walk_builder.add_ignore(pattern);
-        }
+        let files_to_process = walk_directory(dir_path, &args.ignore);
+        println!("Found {} files to scan for duplicates.", files_to_process.len());
 
-        let files_to_process: Vec<_> = walk_builder.build()
-            .filter_map(Result::ok)
-            .filter(|e| e.file_type().map_or(false, |ft| ft.is_file()))
-            .map(|e| e.into_path())
-            .collect();
+        return match find_duplicate_files(files_to_process, args.link, args.delete) {
+            Ok(clusters) => {
+                print_duplicate_clusters(&clusters, args.link, args.delete);
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Error finding duplicate files: {}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    if let Some(dir_path) = args.directory {
+        // Process a directory
+        let files_to_process = walk_directory(&dir_path, &args.ignore);
 
         println!("Found {} files to process in directory.", files_to_process.len());
 
-        for file_path in files_to_process {
-            let output_path = args.output.as_ref().map(|o| {
-                // Create a structured output directory if a single output file is not specified
-                let file_name = file_path.file_name().unwrap();
-                o.join(file_name)
-            });
-
-             match process_file(&file_path, output_path.as_deref()) {
-                Ok(stats) => {
-                    if args.stat {
-                        println!("\nStats for {}:", file_path.display());
-                        print_stats(&stats);
-                    }
-                    total_stats.total_lines += stats.total_lines;
-                    total_stats.duplicate_lines += stats.duplicate_lines;
-                    total_stats.lines_written += stats.lines_written;
-                }
-                Err(e) => eprintln!("Error processing file {}: {}", file_path.display(), e),
-            }
-        }
+        let options = DedupOptions {
+            exact: args.exact,
+            count: args.count,
+            ignore_case: args.ignore_case,
+            adjacent: args.adjacent,
+            nul: args.nul,
+            merge: args.merge,
+        };
+        let jobs = args.jobs.max(1);
+        let jobs = if args.output.is_none() && jobs > 1 {
+            eprintln!(
+                "Note: without --output, deduped files are printed to stdout, so --jobs {} would interleave their output; processing one file at a time instead.",
+                jobs
+            );
+            1
+        } else {
+            jobs
+        };
+        total_stats = process_directory(
+            files_to_process,
+            args.output.as_ref(),
+            options,
+            args.max_memory,
+            args.tempdir.clone(),
+            args.stat,
+            jobs,
+        );
     } else if let Some(file_path) = args.file {
         // Process a single file
-        match process_file(&file_path, args.output.as_deref()) {
+        let options = DedupOptions {
+            exact: args.exact,
+            count: args.count,
+            ignore_case: args.ignore_case,
+            adjacent: args.adjacent,
+            nul: args.nul,
+            merge: args.merge,
+        };
+        match process_file(&file_path, args.output.as_deref(), options, args.max_memory, args.tempdir.as_deref(), None) {
             Ok(stats) => {
                 total_stats = stats;
             }
@@ -108,19 +240,138 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+/// Walks `dir_path`, skipping anything matched by `ignore` globs, and returns every
+/// regular file found. Hidden files are included by default unless `ignore` excludes
+/// them, matching the directory-processing behavior this is factored out of.
+fn walk_directory(dir_path: &Path, ignore: &[String]) -> Vec<PathBuf> {
+    let mut walk_builder = WalkBuilder::new(dir_path);
+    walk_builder.hidden(false);
+
+    for pattern in ignore {
+        walk_builder.add_ignore(pattern);
+    }
+
+    walk_builder.build()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().map_or(false, |ft| ft.is_file()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Processes every file in `files_to_process` across a bounded pool of `jobs` worker
+/// threads, returning the summed `Stats` across all files.
+///
+/// Each file is independent (its own `HashSet` and output path), so files are simply
+/// pulled off a shared queue by whichever worker is free. Per-file stat blocks are
+/// buffered and printed in completion order so two workers finishing at the same time
+/// can't interleave their output.
+fn process_directory(
+    files_to_process: Vec<PathBuf>,
+    output_dir: Option<&PathBuf>,
+    options: DedupOptions,
+    max_memory: usize,
+    tempdir: Option<PathBuf>,
+    show_stat: bool,
+    jobs: usize,
+) -> Stats {
+    let queue = Arc::new(Mutex::new(VecDeque::from(files_to_process)));
+    let multi_progress = Arc::new(MultiProgress::new());
+    let total_stats = Arc::new(Mutex::new(Stats::default()));
+    let (tx, rx) = mpsc::channel::<String>();
+
+    let handles: Vec<_> = (0..jobs)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let multi_progress = Arc::clone(&multi_progress);
+            let total_stats = Arc::clone(&total_stats);
+            let tx = tx.clone();
+            let output_dir = output_dir.cloned();
+            let tempdir = tempdir.clone();
+
+            std::thread::spawn(move || loop {
+                let file_path = match queue.lock().unwrap().pop_front() {
+                    Some(path) => path,
+                    None => break,
+                };
+
+                let output_path = output_dir.as_ref().map(|o| {
+                    // Create a structured output directory if a single output file is not specified
+                    let file_name = file_path.file_name().unwrap();
+                    o.join(file_name)
+                });
+
+                match process_file(&file_path, output_path.as_deref(), options, max_memory, tempdir.as_deref(), Some(&multi_progress)) {
+                    Ok(stats) => {
+                        if show_stat {
+                            let _ = tx.send(format!(
+                                "\nStats for {}:\n{}",
+                                file_path.display(),
+                                format_stats(&stats)
+                            ));
+                        }
+                        let mut total = total_stats.lock().unwrap();
+                        total.total_lines += stats.total_lines;
+                        total.duplicate_lines += stats.duplicate_lines;
+                        total.lines_written += stats.lines_written;
+                        total.distinct_groups += stats.distinct_groups;
+                    }
+                    Err(e) => {
+                        let _ = tx.send(format!("Error processing file {}: {}\n", file_path.display(), e));
+                    }
+                }
+            })
+        })
+        .collect();
+
+    // Drop our own sender so the receiver loop ends once every worker has finished.
+    drop(tx);
+    for block in rx {
+        print!("{}", block);
+    }
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    Arc::try_unwrap(total_stats).unwrap().into_inner().unwrap()
+}
+
 /// Processes a single file to remove duplicate lines, handling various file encodings gracefully.
 ///
-/// This function detects the file encoding and decodes it to UTF-8 on the fly.
+/// This function detects the file encoding and decodes it to UTF-8 on the fly. If
+/// `input_path` is a tar (or tar.gz) archive, it is instead routed to
+/// `process_tar_archive`, which dedups the text inside each entry.
 ///
 /// # Arguments
 ///
 /// * `input_path` - The path to the file to process.
 /// * `output_path` - Optional path to an output file. If None, prints to stdout.
+/// * `options` - Which dedup strategy and uniq-style features to apply.
+/// * `max_memory` - In the default dedup mode, the approximate number of bytes to
+///   hold in the in-memory dedup set before spilling to the external,
+///   disk-bucketed path in `process_file_external` instead. Ignored by
+///   `--count`/`--adjacent`, which always dedup in memory.
+/// * `tempdir` - Where to place the external path's bucket files, if it's used.
+///   Defaults to the system temp directory.
+/// * `multi_progress` - If set, the file's progress bar is rendered as part of this
+///   shared `MultiProgress` instead of standing alone (used when processing a
+///   directory across multiple worker threads).
 ///
 /// # Returns
 ///
 /// A `Result` containing the `Stats` of the operation or an `io::Error`.
-fn process_file(input_path: &Path, output_path: Option<&Path>) -> io::Result<Stats> {
+fn process_file(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    options: DedupOptions,
+    max_memory: usize,
+    tempdir: Option<&Path>,
+    multi_progress: Option<&MultiProgress>,
+) -> io::Result<Stats> {
+    if is_tar_archive(input_path)? {
+        return process_tar_archive(input_path, output_path, options, multi_progress);
+    }
+
     let mut file = File::open(input_path)?;
     let file_size = file.metadata()?.len();
 
@@ -152,7 +403,6 @@ fn process_file(input_path: &Path, output_path: Option<&Path>) -> io::Result<Sta
         Box::new(BufWriter::new(io::stdout()))
     };
 
-    let mut seen_lines = HashSet::new();
     let mut stats = Stats::default();
     let start_time = Instant::now();
 
@@ -161,25 +411,561 @@ fn process_file(input_path: &Path, output_path: Option<&Path>) -> io::Result<Sta
         .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
         .unwrap()
         .progress_chars("#>- "));
+    let pb = match multi_progress {
+        Some(multi_progress) => multi_progress.add(pb),
+        None => pb,
+    };
+
+    let delimiter: u8 = if options.nul { 0 } else { b'\n' };
+    let mut record_buf: Vec<u8> = Vec::new();
+
+    if options.count && !options.adjacent {
+        // The final count for a group isn't known until the whole file has been read,
+        // so global counting mode buffers first-seen lines and their tallies, then
+        // emits everything after the pass completes.
+        let mut groups: Vec<(String, u64)> = Vec::new();
+        let mut index_by_fingerprint: HashMap<u128, usize> = HashMap::new();
+        let mut index_by_line: HashMap<String, usize> = HashMap::new();
 
-    let mut line = String::new();
-    let mut bytes_read = 0;
+        while let Some((record, bytes_read)) = read_record(&mut reader, delimiter, &mut record_buf)? {
+            pb.inc(bytes_read);
+
+            let key = if options.ignore_case { record.to_lowercase() } else { record.clone() };
+
+            stats.total_lines += 1;
+            let index = if options.exact {
+                *index_by_line.entry(key).or_insert_with(|| {
+                    groups.push((record, 0));
+                    groups.len() - 1
+                })
+            } else {
+                let fp = fingerprint(key.as_bytes());
+                *index_by_fingerprint.entry(fp).or_insert_with(|| {
+                    groups.push((record, 0));
+                    groups.len() - 1
+                })
+            };
+            groups[index].1 += 1;
+        }
+
+        stats.distinct_groups = groups.len() as u64;
+        for (original_record, occurrences) in &groups {
+            write_record(&mut writer, &format!("{:>7} {}", occurrences, original_record), delimiter)?;
+            stats.lines_written += 1;
+        }
+        stats.duplicate_lines = stats.total_lines - stats.lines_written;
+    } else if options.adjacent {
+        let mut previous_key: Option<String> = None;
+        let mut pending_record = String::new();
+        let mut pending_count: u64 = 0;
+
+        while let Some((record, bytes_read)) = read_record(&mut reader, delimiter, &mut record_buf)? {
+            pb.inc(bytes_read);
+
+            let key = if options.ignore_case { record.to_lowercase() } else { record.clone() };
+
+            stats.total_lines += 1;
+            if previous_key.as_deref() == Some(key.as_str()) {
+                pending_count += 1;
+                stats.duplicate_lines += 1;
+            } else {
+                if previous_key.is_some() {
+                    write_adjacent_group(&mut writer, &pending_record, pending_count, options.count, delimiter)?;
+                    stats.lines_written += 1;
+                    stats.distinct_groups += 1;
+                }
+                pending_record = record;
+                pending_count = 1;
+                previous_key = Some(key);
+            }
+        }
+
+        if previous_key.is_some() {
+            write_adjacent_group(&mut writer, &pending_record, pending_count, options.count, delimiter)?;
+            stats.lines_written += 1;
+            stats.distinct_groups += 1;
+        }
+    } else {
+        let mut seen_fingerprints: HashSet<u128> = HashSet::new();
+        let mut seen_lines: HashSet<String> = HashSet::new();
+        let mut approx_memory_bytes: usize = 0;
 
-    while reader.read_line(&mut line)? > 0 {
-        bytes_read += line.as_bytes().len() as u64;
-        pb.set_position(bytes_read);
+        // Unique records go straight to `writer` as they're found, so memory is
+        // bounded by the fingerprint/line set, not by the deduplicated text. The one
+        // exception is plain stdout output: if the set crosses `max_memory` partway
+        // through, we restart the whole file through `process_file_external`, and
+        // anything already printed to stdout can't be taken back. A real output file
+        // doesn't have that problem since `process_file_external` just truncates and
+        // rewrites it, so only the stdout case needs a retractable buffer here.
+        let mut output_buffer: Option<Vec<u8>> = if output_path.is_none() { Some(Vec::new()) } else { None };
 
-        let trimmed_line = line.trim_end();
+        while let Some((record, bytes_read)) = read_record(&mut reader, delimiter, &mut record_buf)? {
+            pb.inc(bytes_read);
+
+            let key = if options.ignore_case { record.to_lowercase() } else { record.clone() };
+
+            stats.total_lines += 1;
+            let is_new = if options.exact {
+                let key_len = key.len();
+                let inserted = seen_lines.insert(key);
+                if inserted {
+                    approx_memory_bytes += key_len;
+                }
+                inserted
+            } else {
+                let inserted = seen_fingerprints.insert(fingerprint(key.as_bytes()));
+                if inserted {
+                    approx_memory_bytes += std::mem::size_of::<u128>();
+                }
+                inserted
+            };
+            if is_new {
+                match output_buffer.as_mut() {
+                    Some(buffer) => write_record(buffer, &record, delimiter)?,
+                    None => write_record(&mut writer, &record, delimiter)?,
+                }
+                stats.lines_written += 1;
+                stats.distinct_groups += 1;
+            } else {
+                stats.duplicate_lines += 1;
+            }
+
+            if approx_memory_bytes > max_memory {
+                pb.finish_and_clear();
+                if output_buffer.is_none() {
+                    // The file already holds everything written so far; dropping the
+                    // writer lets `process_file_external` reopen and truncate it.
+                    drop(writer);
+                }
+                let spill_dir = match tempdir {
+                    Some(dir) => dir.to_path_buf(),
+                    None => std::env::temp_dir(),
+                };
+                return process_file_external(input_path, output_path, options, &spill_dir, multi_progress);
+            }
+        }
+
+        if let Some(buffer) = output_buffer {
+            writer.write_all(&buffer)?;
+        }
+    }
+
+    pb.finish_with_message("done");
+    stats.duration = start_time.elapsed();
+    Ok(stats)
+}
+
+/// Number of on-disk buckets the external dedup path shards lines into.
+const EXTERNAL_DEDUP_BUCKETS: u128 = 64;
+
+/// Bytes read from the start of the input to sniff its encoding in
+/// `process_file_external`, rather than buffering the whole (potentially huge) file.
+const ENCODING_SNIFF_BYTES: u64 = 64 * 1024;
+
+/// Writes one `(sequence, line)` record to a bucket or survivor file: an 8-byte LE
+/// sequence number, a 4-byte LE length, then the raw line bytes.
+fn write_bucket_record(writer: &mut impl Write, sequence: u64, line: &[u8]) -> io::Result<()> {
+    writer.write_all(&sequence.to_le_bytes())?;
+    writer.write_all(&(line.len() as u32).to_le_bytes())?;
+    writer.write_all(line)
+}
+
+/// Reads the next `(sequence, line)` record written by `write_bucket_record`, or
+/// `None` at EOF.
+fn read_bucket_record(reader: &mut impl Read) -> io::Result<Option<(u64, Vec<u8>)>> {
+    let mut sequence_buf = [0u8; 8];
+    match reader.read_exact(&mut sequence_buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let sequence = u64::from_le_bytes(sequence_buf);
+
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf)?;
+    let len = u32::from_le_bytes(len_buf) as usize;
+
+    let mut line = vec![0u8; len];
+    reader.read_exact(&mut line)?;
+    Ok(Some((sequence, line)))
+}
+
+/// Reads the next surviving record from each of `survivor_paths` in lockstep, always
+/// emitting the one with the lowest sequence number next, so the merged output comes
+/// back out in the same order the lines were first read in.
+fn merge_survivor_buckets(survivor_paths: &[PathBuf], delimiter: u8, writer: &mut dyn Write) -> io::Result<()> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    let mut readers: Vec<BufReader<File>> = survivor_paths
+        .iter()
+        .map(|path| Ok(BufReader::new(File::open(path)?)))
+        .collect::<io::Result<_>>()?;
+
+    let mut heap: BinaryHeap<Reverse<(u64, usize, Vec<u8>)>> = BinaryHeap::new();
+    for (bucket_index, reader) in readers.iter_mut().enumerate() {
+        if let Some((sequence, line)) = read_bucket_record(reader)? {
+            heap.push(Reverse((sequence, bucket_index, line)));
+        }
+    }
+
+    while let Some(Reverse((_, bucket_index, line))) = heap.pop() {
+        write_record(writer, &String::from_utf8_lossy(&line), delimiter)?;
+        if let Some((sequence, next_line)) = read_bucket_record(&mut readers[bucket_index])? {
+            heap.push(Reverse((sequence, bucket_index, next_line)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Disk-spilling dedup path for inputs too large to hold a full fingerprint set in
+/// memory (reached automatically from `process_file` once `max_memory` is exceeded).
+///
+/// Each line is routed by `fingerprint(line) % EXTERNAL_DEDUP_BUCKETS` into one of a
+/// fixed number of on-disk bucket files, tagged with a monotonically increasing
+/// sequence number. Since every copy of a given line lands in the same bucket, each
+/// bucket can then be deduplicated independently with a small in-memory set; the
+/// surviving records from all buckets are finally merged back into one stream ordered
+/// by sequence number, so first-seen order is preserved exactly as in the in-memory
+/// path. All bucket and survivor files live under a dedicated subdirectory of
+/// `tempdir`, removed once processing finishes successfully.
+///
+/// Like `process_tar_archive`, this only supports the default global dedup
+/// (`--exact`/fingerprint plus `--ignore-case`): `--count` and `--adjacent` need the
+/// whole stream's tally or strict adjacency before they can emit anything, which
+/// doesn't compose with per-bucket processing.
+fn process_file_external(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    options: DedupOptions,
+    tempdir: &Path,
+    multi_progress: Option<&MultiProgress>,
+) -> io::Result<Stats> {
+    if options.count || options.adjacent {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--count and --adjacent are not supported once input exceeds --max-memory",
+        ));
+    }
+
+    let work_dir = unique_spill_dir(tempdir)?;
+
+    let file_size = File::open(input_path)?.metadata()?.len();
+    let pb = ProgressBar::new(file_size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>- "));
+    let pb = match multi_progress {
+        Some(multi_progress) => multi_progress.add(pb),
+        None => pb,
+    };
+
+    // Sampled, not the whole file: this path exists for inputs too large to fit in
+    // memory, so slurping all of it just to sniff the encoding would defeat the point.
+    let mut buffer = Vec::new();
+    File::open(input_path)?.take(ENCODING_SNIFF_BYTES).read_to_end(&mut buffer)?;
+    let (encoding, ..) = chardet::detect(&buffer);
+    let encoding = encoding_rs::Encoding::for_label(encoding.as_bytes()).unwrap_or(encoding_rs::UTF_8);
+
+    let mut reader = BufReader::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(File::open(input_path)?)
+    );
+
+    let delimiter: u8 = if options.nul { 0 } else { b'\n' };
+    let mut stats = Stats::default();
+    let start_time = Instant::now();
+
+    // Phase 1: shard every record into its bucket file by fingerprint, tagged with
+    // the sequence number it was read in.
+    let mut bucket_writers: Vec<BufWriter<File>> = (0..EXTERNAL_DEDUP_BUCKETS)
+        .map(|i| -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::new(File::create(work_dir.join(format!("bucket-{i}")))?))
+        })
+        .collect::<io::Result<_>>()?;
+
+    let mut record_buf: Vec<u8> = Vec::new();
+    let mut sequence: u64 = 0;
+    while let Some((record, bytes_read)) = read_record(&mut reader, delimiter, &mut record_buf)? {
+        pb.inc(bytes_read);
+        stats.total_lines += 1;
+
+        let key = if options.ignore_case { record.to_lowercase() } else { record.clone() };
+        let bucket = (fingerprint(key.as_bytes()) % EXTERNAL_DEDUP_BUCKETS) as usize;
+        write_bucket_record(&mut bucket_writers[bucket], sequence, record.as_bytes())?;
+        sequence += 1;
+    }
+    for bucket_writer in &mut bucket_writers {
+        bucket_writer.flush()?;
+    }
+    drop(bucket_writers);
+
+    // Phase 2: dedup each bucket independently (it fits in memory even though the
+    // whole file doesn't), writing first-seen records to a survivor file in the same
+    // relative order they arrived in.
+    let mut survivor_paths = Vec::with_capacity(EXTERNAL_DEDUP_BUCKETS as usize);
+    for i in 0..EXTERNAL_DEDUP_BUCKETS {
+        let bucket_path = work_dir.join(format!("bucket-{i}"));
+        let survivor_path = work_dir.join(format!("survivors-{i}"));
+        let mut bucket_reader = BufReader::new(File::open(&bucket_path)?);
+        let mut survivor_writer = BufWriter::new(File::create(&survivor_path)?);
+
+        let mut seen_fingerprints: HashSet<u128> = HashSet::new();
+        let mut seen_lines: HashSet<String> = HashSet::new();
+
+        while let Some((sequence, line_bytes)) = read_bucket_record(&mut bucket_reader)? {
+            let line = String::from_utf8_lossy(&line_bytes).into_owned();
+            let key = if options.ignore_case { line.to_lowercase() } else { line };
+
+            let is_new = if options.exact {
+                seen_lines.insert(key)
+            } else {
+                seen_fingerprints.insert(fingerprint(key.as_bytes()))
+            };
+            if is_new {
+                write_bucket_record(&mut survivor_writer, sequence, &line_bytes)?;
+                stats.lines_written += 1;
+                stats.distinct_groups += 1;
+            } else {
+                stats.duplicate_lines += 1;
+            }
+        }
+        survivor_writer.flush()?;
+        survivor_paths.push(survivor_path);
+    }
+
+    // Phase 3: merge surviving records back into their original order.
+    let mut writer: Box<dyn Write> = if let Some(path) = output_path {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Box::new(BufWriter::new(File::create(path)?))
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+    merge_survivor_buckets(&survivor_paths, delimiter, &mut writer)?;
+    writer.flush()?;
+    let _ = std::fs::remove_dir_all(&work_dir);
+
+    pb.finish_with_message("done");
+    stats.duration = start_time.elapsed();
+    Ok(stats)
+}
+
+/// Creates and returns a fresh, empty subdirectory of `tempdir` to hold one
+/// `process_file_external` run's bucket and survivor files, named so concurrent
+/// runs (e.g. sibling workers in `process_directory`) never collide.
+fn unique_spill_dir(tempdir: &Path) -> io::Result<PathBuf> {
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = tempdir.join(format!("dedupler-spill-{}-{}", std::process::id(), id));
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Gzip's two-byte magic number.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns true if `path` starts with the gzip magic number.
+fn has_gzip_magic(path: &Path) -> io::Result<bool> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(false),
+    };
+    let mut magic = [0u8; 2];
+    Ok(file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC)
+}
+
+/// Returns true if `path` looks like a tar or tar.gz archive, by extension first and
+/// then by magic bytes so extensionless archives are still detected: a bare tar is
+/// recognized by the `ustar` magic at offset 257 of its first header, and a gzip
+/// stream is only treated as a tar.gz if *decompressing* it also shows that same
+/// `ustar` magic — gzip's `1f 8b` alone isn't enough, since an ordinary gzipped
+/// non-tar file (e.g. a gzipped text file) would otherwise be misrouted into the tar
+/// path and fail to parse.
+fn is_tar_archive(path: &Path) -> io::Result<bool> {
+    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+        if name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            return Ok(true);
+        }
+    }
+
+    if has_gzip_magic(path)? {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Ok(false),
+        };
+        let mut header = [0u8; 262];
+        let mut decoder = GzDecoder::new(file);
+        return Ok(decoder.read_exact(&mut header).is_ok() && &header[257..262] == b"ustar");
+    }
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(false),
+    };
+    if file.seek(SeekFrom::Start(257)).is_ok() {
+        let mut ustar_magic = [0u8; 5];
+        if file.read_exact(&mut ustar_magic).is_ok() && &ustar_magic == b"ustar" {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Per-stream dedup state. Kept separate from `Stats` so that `--merge` can carry it
+/// across tar entries while each entry still contributes to the same running stats.
+#[derive(Default)]
+struct DedupState {
+    seen_fingerprints: HashSet<u128>,
+    seen_lines: HashSet<String>,
+}
+
+/// Dedups one text stream into `writer` using `options.exact`/`options.ignore_case`,
+/// folding results into `state` and `stats`. Shared by tar entry processing so that
+/// `--merge` can pass the same `state` to every entry.
+///
+/// Unlike `process_file`, this does not support `--count` or `--adjacent`: those modes
+/// need to see a whole entry (or the whole merged stream) before they can emit
+/// anything, which doesn't compose with writing each entry back into the output
+/// archive as it's read. Archive input is rejected upfront when those flags are set.
+fn dedup_entry(
+    reader: &mut impl BufRead,
+    writer: &mut dyn Write,
+    options: DedupOptions,
+    state: &mut DedupState,
+    stats: &mut Stats,
+) -> io::Result<()> {
+    let delimiter: u8 = if options.nul { 0 } else { b'\n' };
+    let mut buf: Vec<u8> = Vec::new();
+
+    while let Some((record, _)) = read_record(reader, delimiter, &mut buf)? {
+        let key = if options.ignore_case { record.to_lowercase() } else { record.clone() };
 
         stats.total_lines += 1;
-        if seen_lines.insert(trimmed_line.to_string()) {
-            writeln!(writer, "{}", trimmed_line)?;
+        let is_new = if options.exact {
+            state.seen_lines.insert(key)
+        } else {
+            state.seen_fingerprints.insert(fingerprint(key.as_bytes()))
+        };
+        if is_new {
+            write_record(writer, &record, delimiter)?;
             stats.lines_written += 1;
+            stats.distinct_groups += 1;
         } else {
             stats.duplicate_lines += 1;
         }
+    }
+
+    Ok(())
+}
+
+/// Dedups the text inside every regular-file entry of a tar (or tar.gz) archive,
+/// writing a parallel output archive with the same entries but deduplicated content.
+/// Non-regular entries (directories, symlinks, hard links, ...) carry no text to dedup
+/// and are copied through unchanged so the output keeps the same archive structure. If
+/// the input was gzipped, the output is re-gzipped too.
+///
+/// With `options.merge`, every entry shares one dedup set, so a line that appeared in
+/// an earlier entry is dropped from a later one too. Without it, each entry gets its
+/// own fresh set and is deduplicated independently.
+fn process_tar_archive(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    options: DedupOptions,
+    multi_progress: Option<&MultiProgress>,
+) -> io::Result<Stats> {
+    if options.count || options.adjacent {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--count and --adjacent are not supported for tar archive input",
+        ));
+    }
+
+    let file_size = File::open(input_path)?.metadata()?.len();
+    let pb = ProgressBar::new(file_size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>- "));
+    let pb = match multi_progress {
+        Some(multi_progress) => multi_progress.add(pb),
+        None => pb,
+    };
+
+    let is_gzipped = has_gzip_magic(input_path)?;
+    let file = File::open(input_path)?;
+    let reader: Box<dyn Read> = if is_gzipped {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut archive = Archive::new(reader);
+
+    let mut output_bytes: Vec<u8> = Vec::new();
+    let mut builder = Builder::new(&mut output_bytes);
+
+    let mut stats = Stats::default();
+    let start_time = Instant::now();
+    let mut state = DedupState::default();
+
+    for entry_result in archive.entries()? {
+        let mut entry = entry_result?;
+        if !entry.header().entry_type().is_file() {
+            let header = entry.header().clone();
+            pb.inc(header.size().unwrap_or(0));
+            builder.append(&header, &mut entry)?;
+            continue;
+        }
+        let entry_path = entry.path()?.into_owned();
+
+        let mut raw = Vec::new();
+        entry.read_to_end(&mut raw)?;
+        pb.inc(raw.len() as u64);
+
+        let (encoding, ..) = chardet::detect(&raw);
+        let encoding = encoding_rs::Encoding::for_label(encoding.as_bytes()).unwrap_or(encoding_rs::UTF_8);
+        let mut decoded_reader = BufReader::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding))
+                .build(&raw[..])
+        );
+
+        if !options.merge {
+            state = DedupState::default();
+        }
 
-        line.clear();
+        let mut entry_output: Vec<u8> = Vec::new();
+        dedup_entry(&mut decoded_reader, &mut entry_output, options, &mut state, &mut stats)?;
+
+        let mut header = entry.header().clone();
+        header.set_size(entry_output.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, &entry_path, &entry_output[..])?;
+    }
+
+    builder.into_inner()?;
+
+    let output_bytes: Vec<u8> = if is_gzipped {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&output_bytes)?;
+        encoder.finish()?
+    } else {
+        output_bytes
+    };
+
+    match output_path {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, &output_bytes)?;
+        }
+        None => io::stdout().write_all(&output_bytes)?,
     }
 
     pb.finish_with_message("done");
@@ -187,12 +973,208 @@ fn process_file(input_path: &Path, output_path: Option<&Path>) -> io::Result<Sta
     Ok(stats)
 }
 
+/// Number of leading bytes hashed in the first pass of duplicate-file detection.
+const PARTIAL_HASH_BYTES: usize = 4096;
+
+/// A group of files confirmed to have byte-identical contents. `canonical` is the one
+/// kept when `--link`/`--delete` is set; every other member is a redundant copy.
+#[derive(Debug)]
+struct DuplicateCluster {
+    canonical: PathBuf,
+    duplicates: Vec<PathBuf>,
+}
+
+/// Computes the 128-bit SipHash-1-3 fingerprint of a file's contents, reading at most
+/// `max_bytes` bytes when given (used for the cheap first-pass partial hash) or the
+/// whole file when `None` (used to confirm true duplicates).
+fn hash_file(path: &Path, max_bytes: Option<usize>) -> io::Result<u128> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = SipHasher13::new_with_keys(FINGERPRINT_KEY.0, FINGERPRINT_KEY.1);
+    let mut buf = [0u8; 8192];
+    let mut remaining = max_bytes;
+
+    loop {
+        let want = match remaining {
+            Some(0) => break,
+            Some(n) => n.min(buf.len()),
+            None => buf.len(),
+        };
+        let n = reader.read(&mut buf[..want])?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+        if let Some(r) = remaining.as_mut() {
+            *r -= n;
+        }
+    }
+
+    Ok(hasher.finish128().as_u128())
+}
+
+/// Replaces `duplicate` with a hard link to `canonical` without ever leaving a moment
+/// where `duplicate` doesn't exist: links to a sibling temp path first, then atomically
+/// renames it over `duplicate`. If `canonical` and `duplicate` live on different
+/// filesystems, `hard_link` fails and `duplicate` is left untouched.
+fn replace_with_hard_link(canonical: &Path, duplicate: &Path) -> io::Result<()> {
+    let temp_path = duplicate.with_extension(format!("dedupler-tmp-{}", std::process::id()));
+    std::fs::hard_link(canonical, &temp_path)?;
+    std::fs::rename(&temp_path, duplicate)?;
+    Ok(())
+}
+
+/// Finds clusters of byte-identical files among `files` using the standard two-phase
+/// hash (as the ddh tool does): group by `(size, partial_hash)` first to cheaply rule
+/// out files that can't possibly match, then confirm true duplicates within each
+/// candidate group with a full-file hash, since equal size and equal head don't prove
+/// identity.
+///
+/// With `link` or `delete` set, every non-canonical member of a confirmed cluster is
+/// replaced with a hard link to (or simply removed in favor of) the member whose path
+/// sorts first.
+fn find_duplicate_files(files: Vec<PathBuf>, link: bool, delete: bool) -> io::Result<Vec<DuplicateCluster>> {
+    let mut by_size_and_partial_hash: HashMap<(u64, u128), Vec<PathBuf>> = HashMap::new();
+
+    for path in files {
+        let size = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => continue,
+        };
+        let partial_hash = match hash_file(&path, Some(PARTIAL_HASH_BYTES)) {
+            Ok(hash) => hash,
+            Err(_) => continue,
+        };
+        by_size_and_partial_hash.entry((size, partial_hash)).or_default().push(path);
+    }
+
+    let mut clusters = Vec::new();
+    for (_, candidates) in by_size_and_partial_hash {
+        if candidates.len() < 2 {
+            continue;
+        }
+
+        let mut by_full_hash: HashMap<u128, Vec<PathBuf>> = HashMap::new();
+        for path in candidates {
+            if let Ok(hash) = hash_file(&path, None) {
+                by_full_hash.entry(hash).or_default().push(path);
+            }
+        }
+
+        for (_, mut paths) in by_full_hash {
+            if paths.len() < 2 {
+                continue;
+            }
+            paths.sort();
+            let canonical = paths.remove(0);
+            clusters.push(DuplicateCluster { canonical, duplicates: paths });
+        }
+    }
+    clusters.sort_by(|a, b| a.canonical.cmp(&b.canonical));
+
+    if link || delete {
+        for cluster in &clusters {
+            for duplicate in &cluster.duplicates {
+                let result = if link {
+                    replace_with_hard_link(&cluster.canonical, duplicate)
+                } else {
+                    std::fs::remove_file(duplicate)
+                };
+                if let Err(e) = result {
+                    eprintln!("Error processing duplicate {}: {}", duplicate.display(), e);
+                }
+            }
+        }
+    }
+
+    Ok(clusters)
+}
+
+/// Prints each duplicate-file cluster, noting whether its redundant copies were
+/// hard-linked or deleted, or are just being reported.
+fn print_duplicate_clusters(clusters: &[DuplicateCluster], link: bool, delete: bool) {
+    if clusters.is_empty() {
+        println!("No duplicate files found.");
+        return;
+    }
+
+    for (index, cluster) in clusters.iter().enumerate() {
+        println!("\nCluster {}: {}", index + 1, cluster.canonical.display());
+        for duplicate in &cluster.duplicates {
+            let action = if delete {
+                "deleted"
+            } else if link {
+                "hard-linked to canonical"
+            } else {
+                "duplicate"
+            };
+            println!("  {} ({})", duplicate.display(), action);
+        }
+    }
+
+    println!(
+        "\n{} duplicate file(s) found across {} cluster(s).",
+        clusters.iter().map(|c| c.duplicates.len()).sum::<usize>(),
+        clusters.len()
+    );
+}
+
+/// Reads the next record from `reader`, delimited by `delimiter` (`\n` normally, NUL
+/// with `--nul`). Newline-delimited records have their trailing whitespace trimmed;
+/// NUL-delimited records only have the trailing NUL stripped, since embedded
+/// whitespace (e.g. in `find -print0` paths) is significant. Returns `None` at EOF.
+fn read_record(
+    reader: &mut impl BufRead,
+    delimiter: u8,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<(String, u64)>> {
+    buf.clear();
+    let bytes_read = reader.read_until(delimiter, buf)?;
+    if bytes_read == 0 {
+        return Ok(None);
+    }
+
+    if buf.last() == Some(&delimiter) {
+        buf.pop();
+    }
+
+    let record = String::from_utf8_lossy(buf).into_owned();
+    let record = if delimiter == 0 { record } else { record.trim_end().to_string() };
+    Ok(Some((record, bytes_read as u64)))
+}
+
+/// Writes a single record followed by `delimiter`.
+fn write_record(writer: &mut dyn Write, record: &str, delimiter: u8) -> io::Result<()> {
+    writer.write_all(record.as_bytes())?;
+    writer.write_all(&[delimiter])
+}
+
+/// Writes one collapsed group of consecutive duplicate lines in `--adjacent` mode,
+/// prefixing the occurrence count when `count` is enabled.
+fn write_adjacent_group(
+    writer: &mut dyn Write,
+    record: &str,
+    occurrences: u64,
+    count: bool,
+    delimiter: u8,
+) -> io::Result<()> {
+    if count {
+        write_record(writer, &format!("{:>7} {}", occurrences, record), delimiter)
+    } else {
+        write_record(writer, record, delimiter)
+    }
+}
+
+/// Formats the statistics for display.
+fn format_stats(stats: &Stats) -> String {
+    format!(
+        "  Total lines read: {}\n  Duplicate lines found: {}\n  Lines written: {}\n  Distinct groups: {}\n  Duration: {:.2?}\n",
+        stats.total_lines, stats.duplicate_lines, stats.lines_written, stats.distinct_groups, stats.duration
+    )
+}
+
 /// Prints the statistics to the console.
 fn print_stats(stats: &Stats) {
-    println!("  Total lines read: {}", stats.total_lines);
-    println!("  Duplicate lines found: {}", stats.duplicate_lines);
-    println!("  Lines written: {}", stats.lines_written);
-    println!("  Duration: {:.2?}", stats.duration);
+    print!("{}", format_stats(stats));
 }
 
 
@@ -200,7 +1182,7 @@ fn print_stats(stats: &Stats) {
 mod tests {
     use super::*;
     use std::fs;
-    use tempfile::NamedTempFile;
+    use tempfile::{tempdir, NamedTempFile};
 
     // Helper function to create a temporary file with content
     fn create_temp_file(content: &str) -> io::Result<NamedTempFile> {
@@ -221,7 +1203,7 @@ mod tests {
         let input_file = create_temp_file("line1\nline2\nline3")?;
         let output_file = NamedTempFile::new()?;
 
-        let stats = process_file(input_file.path(), Some(output_file.path()))?;
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
 
         assert_eq!(stats.total_lines, 3);
         assert_eq!(stats.duplicate_lines, 0);
@@ -238,7 +1220,7 @@ mod tests {
         let input_file = create_temp_file("apple\nbanana\napple\norange\nbanana")?;
         let output_file = NamedTempFile::new()?;
 
-        let stats = process_file(input_file.path(), Some(output_file.path()))?;
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
 
         assert_eq!(stats.total_lines, 5);
         assert_eq!(stats.duplicate_lines, 2);
@@ -255,7 +1237,7 @@ mod tests {
         let input_file = create_temp_file("")?;
         let output_file = NamedTempFile::new()?;
 
-        let stats = process_file(input_file.path(), Some(output_file.path()))?;
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
 
         assert_eq!(stats.total_lines, 0);
         assert_eq!(stats.duplicate_lines, 0);
@@ -272,7 +1254,7 @@ mod tests {
         let input_file = create_temp_file("a\n\nb\n\na")?;
         let output_file = NamedTempFile::new()?;
 
-        let stats = process_file(input_file.path(), Some(output_file.path()))?;
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
 
         // The lines are: "a", "", "b", "", "a"
         assert_eq!(stats.total_lines, 5);
@@ -292,7 +1274,7 @@ mod tests {
         let input_file = create_temp_file_bytes(content)?;
         let output_file = NamedTempFile::new()?;
 
-        let stats = process_file(input_file.path(), Some(output_file.path()))?;
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
 
         assert_eq!(stats.total_lines, 3);
         assert_eq!(stats.duplicate_lines, 1);
@@ -303,4 +1285,296 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_process_file_exact_matches_fingerprint_mode() -> io::Result<()> {
+        let input_file = create_temp_file("apple\nbanana\napple\norange\nbanana")?;
+        let output_file = NamedTempFile::new()?;
+
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions { exact: true, ..Default::default() }, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 5);
+        assert_eq!(stats.duplicate_lines, 2);
+        assert_eq!(stats.lines_written, 3);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "apple\nbanana\norange\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_with_count() -> io::Result<()> {
+        let input_file = create_temp_file("apple\nbanana\napple\norange\nbanana\napple")?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { count: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 6);
+        assert_eq!(stats.lines_written, 3);
+        assert_eq!(stats.distinct_groups, 3);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "      3 apple\n      2 banana\n      1 orange\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_with_ignore_case() -> io::Result<()> {
+        let input_file = create_temp_file("Apple\napple\nBANANA\nbanana")?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { ignore_case: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 4);
+        assert_eq!(stats.lines_written, 2);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "Apple\nBANANA\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_adjacent_only_collapses_consecutive_duplicates() -> io::Result<()> {
+        let input_file = create_temp_file("a\na\nb\na\na\na")?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { adjacent: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 6);
+        assert_eq!(stats.distinct_groups, 3);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "a\nb\na\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_adjacent_with_count() -> io::Result<()> {
+        let input_file = create_temp_file("a\na\nb\na\na\na")?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { adjacent: true, count: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.distinct_groups, 3);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "      2 a\n      1 b\n      3 a\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable_and_distinguishes_lines() {
+        assert_eq!(fingerprint(b"apple"), fingerprint(b"apple"));
+        assert_ne!(fingerprint(b"apple"), fingerprint(b"banana"));
+    }
+
+    #[test]
+    fn test_process_file_with_nul_preserves_embedded_newlines() -> io::Result<()> {
+        let input_file = create_temp_file_bytes(b"apple\nstill apple\0banana\0apple\nstill apple\0")?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { nul: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 3);
+        assert_eq!(stats.duplicate_lines, 1);
+        assert_eq!(stats.lines_written, 2);
+
+        let output_content = fs::read(output_file.path())?;
+        assert_eq!(output_content, b"apple\nstill apple\0banana\0");
+
+        Ok(())
+    }
+
+    fn append_tar_entry(builder: &mut Builder<&mut Vec<u8>>, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data)
+    }
+
+    fn build_tar_bytes(entries: &[(&str, &[u8])]) -> io::Result<Vec<u8>> {
+        let mut tar_bytes = Vec::new();
+        {
+            let mut builder = Builder::new(&mut tar_bytes);
+            for (name, data) in entries {
+                append_tar_entry(&mut builder, name, data)?;
+            }
+            builder.into_inner()?;
+        }
+        Ok(tar_bytes)
+    }
+
+    #[test]
+    fn test_process_tar_archive_dedups_each_entry_independently() -> io::Result<()> {
+        let tar_bytes = build_tar_bytes(&[
+            ("a.txt", b"apple\nbanana\napple\n"),
+            ("b.txt", b"apple\ncherry\n"),
+        ])?;
+        let input_file = create_temp_file_bytes(&tar_bytes)?;
+        let output_file = NamedTempFile::new()?;
+
+        let stats = process_file(input_file.path(), Some(output_file.path()), DedupOptions::default(), usize::MAX, None, None)?;
+
+        // "apple" is a duplicate within a.txt but appears fresh again in b.txt since
+        // each entry gets its own dedup set by default.
+        assert_eq!(stats.total_lines, 5);
+        assert_eq!(stats.lines_written, 4);
+
+        let output_bytes = fs::read(output_file.path())?;
+        let mut archive = Archive::new(&output_bytes[..]);
+        let mut contents = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let mut data = String::new();
+            entry.read_to_string(&mut data)?;
+            contents.push((path.to_string_lossy().into_owned(), data));
+        }
+
+        assert_eq!(contents, vec![
+            ("a.txt".to_string(), "apple\nbanana\n".to_string()),
+            ("b.txt".to_string(), "apple\ncherry\n".to_string()),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_tar_archive_with_merge_dedups_across_entries() -> io::Result<()> {
+        let tar_bytes = build_tar_bytes(&[
+            ("a.txt", b"apple\nbanana\n"),
+            ("b.txt", b"apple\ncherry\n"),
+        ])?;
+        let input_file = create_temp_file_bytes(&tar_bytes)?;
+        let output_file = NamedTempFile::new()?;
+
+        let options = DedupOptions { merge: true, ..Default::default() };
+        let stats = process_file(input_file.path(), Some(output_file.path()), options, usize::MAX, None, None)?;
+
+        assert_eq!(stats.total_lines, 4);
+        assert_eq!(stats.lines_written, 3);
+
+        let output_bytes = fs::read(output_file.path())?;
+        let mut archive = Archive::new(&output_bytes[..]);
+        let mut contents = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut data = String::new();
+            entry.read_to_string(&mut data)?;
+            contents.push(data);
+        }
+
+        assert_eq!(contents, vec!["apple\nbanana\n".to_string(), "cherry\n".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_duplicate_files_clusters_identical_content() -> io::Result<()> {
+        let dir = tempdir()?;
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        let c = dir.path().join("c.txt");
+        fs::write(&a, b"same content")?;
+        fs::write(&b, b"same content")?;
+        fs::write(&c, b"different content")?;
+
+        let clusters = find_duplicate_files(vec![a.clone(), b.clone(), c.clone()], false, false)?;
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].canonical, a);
+        assert_eq!(clusters[0].duplicates, vec![b]);
+        assert!(a.exists());
+        assert!(c.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_duplicate_files_ignores_same_size_different_content() -> io::Result<()> {
+        let dir = tempdir()?;
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, b"aaaa")?;
+        fs::write(&b, b"bbbb")?;
+
+        let clusters = find_duplicate_files(vec![a, b], false, false)?;
+
+        assert!(clusters.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_duplicate_files_with_delete_removes_redundant_copies() -> io::Result<()> {
+        let dir = tempdir()?;
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, b"same content")?;
+        fs::write(&b, b"same content")?;
+
+        let clusters = find_duplicate_files(vec![a.clone(), b.clone()], false, true)?;
+
+        assert_eq!(clusters.len(), 1);
+        assert!(a.exists());
+        assert!(!b.exists());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_duplicate_files_with_link_hardlinks_redundant_copies() -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir()?;
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, b"same content")?;
+        fs::write(&b, b"same content")?;
+
+        find_duplicate_files(vec![a.clone(), b.clone()], true, false)?;
+
+        assert_eq!(fs::metadata(&a)?.ino(), fs::metadata(&b)?.ino());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_spills_to_external_dedup_when_max_memory_exceeded() -> io::Result<()> {
+        let input_file = create_temp_file("apple\nbanana\napple\ncherry\nbanana\ndate")?;
+        let output_file = NamedTempFile::new()?;
+        let spill_dir = tempdir()?;
+
+        // A 1-byte budget is blown by the very first unique line, forcing every run
+        // through process_file_external regardless of its actual content.
+        let stats = process_file(
+            input_file.path(),
+            Some(output_file.path()),
+            DedupOptions::default(),
+            1,
+            Some(spill_dir.path()),
+            None,
+        )?;
+
+        assert_eq!(stats.total_lines, 6);
+        assert_eq!(stats.duplicate_lines, 2);
+        assert_eq!(stats.lines_written, 4);
+
+        let output_content = fs::read_to_string(output_file.path())?;
+        assert_eq!(output_content, "apple\nbanana\ncherry\ndate\n");
+
+        Ok(())
+    }
 }
\ No newline at end of file